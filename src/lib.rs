@@ -1,22 +1,25 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 use pinocchio::{
-    account_info::AccountInfo, 
-    entrypoint, 
-    instruction::{Seed, Signer}, 
-    nostd_panic_handler, 
-    program_error::ProgramError, 
-    pubkey::{find_program_address, Pubkey}, 
+    account_info::AccountInfo,
+    cpi::invoke_signed,
+    entrypoint,
+    instruction::{AccountMeta, Instruction, Seed, Signer},
+    nostd_panic_handler,
+    program_error::ProgramError,
+    pubkey::{find_program_address, Pubkey},
     sysvars::{
-        clock::Clock, 
-        instructions::{Instructions}, 
+        clock::Clock,
+        instructions::{Instructions},
+        rent::Rent,
         Sysvar
-    }, 
+    },
     ProgramResult
 };
 
 use pinocchio::sysvars::instructions::IntrospectedInstruction;
 use pinocchio_secp256r1_instruction::{Secp256r1Instruction, Secp256r1Pubkey};
-use pinocchio_system::instructions::Transfer;
+use pinocchio_system::instructions::{CreateAccount, Transfer};
+use pinocchio_token::{instructions::Transfer as TokenTransfer, state::TokenAccount};
 
 entrypoint!(process_instruction);
 nostd_panic_handler!();
@@ -37,6 +40,12 @@ fn process_instruction(
     match instruction_data.split_first() {
         Some((Deposit::DISCRIMINATOR, data)) => Deposit::try_from((data, accounts))?.process(),
         Some((Withdraw::DISCRIMINATOR, data)) => Withdraw::try_from((data, accounts))?.process(),
+        Some((TokenDeposit::DISCRIMINATOR, data)) => TokenDeposit::try_from((data, accounts))?.process(),
+        Some((TokenWithdraw::DISCRIMINATOR, data)) => TokenWithdraw::try_from((data, accounts))?.process(),
+        Some((ConfigureMultisig::DISCRIMINATOR, data)) => ConfigureMultisig::try_from((data, accounts))?.process(),
+        Some((MultisigWithdraw::DISCRIMINATOR, data)) => MultisigWithdraw::try_from((data, accounts))?.process(),
+        Some((ConfigureRelay::DISCRIMINATOR, data)) => ConfigureRelay::try_from((data, accounts))?.process(),
+        Some((RelayCpi::DISCRIMINATOR, data)) => RelayCpi::try_from((data, accounts))?.process(),
         _ => Err(ProgramError::InvalidAccountData),
     }
 }
@@ -44,13 +53,15 @@ fn process_instruction(
 pub struct DepositAccount<'a> {
     pub payer: &'a AccountInfo,
     pub vault: &'a AccountInfo,
+    pub vesting: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
 }
 
 impl<'a> TryFrom<&'a [AccountInfo]> for DepositAccount<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
-        let [payer, vault, _] = accounts else {
+        let [payer, vault, vesting, system_program] = accounts else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
@@ -66,14 +77,20 @@ impl<'a> TryFrom<&'a [AccountInfo]> for DepositAccount<'a> {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        Ok(Self { payer, vault })
+        if vesting.lamports().ne(&0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self { payer, vault, vesting, system_program })
     }
 }
 
 #[repr(C, packed)]
 pub struct DepositInstructionData {
     pub pubkey: Secp256r1Pubkey,
-    pub amount: u64
+    pub amount: u64,
+    pub start_ts: i64,
+    pub end_ts: i64,
 }
 
 impl<'a> TryFrom<&'a [u8]> for DepositInstructionData {
@@ -84,11 +101,15 @@ impl<'a> TryFrom<&'a [u8]> for DepositInstructionData {
             return Err(ProgramError::InvalidInstructionData);
         }
 
-        let (pubkey_bytes, amount_bytes) = data.split_at(size_of::<Secp256r1Pubkey>());
+        let (pubkey_bytes, rest) = data.split_at(size_of::<Secp256r1Pubkey>());
+        let (amount_bytes, rest) = rest.split_at(size_of::<u64>());
+        let (start_ts_bytes, end_ts_bytes) = rest.split_at(size_of::<i64>());
 
-        Ok(Self { 
-            pubkey: pubkey_bytes.try_into().unwrap(), 
-            amount: u64::from_le_bytes(amount_bytes.try_into().unwrap()) 
+        Ok(Self {
+            pubkey: pubkey_bytes.try_into().unwrap(),
+            amount: u64::from_le_bytes(amount_bytes.try_into().unwrap()),
+            start_ts: i64::from_le_bytes(start_ts_bytes.try_into().unwrap()),
+            end_ts: i64::from_le_bytes(end_ts_bytes.try_into().unwrap()),
         })
     }
 }
@@ -126,6 +147,47 @@ impl<'a> Deposit<'a> {
             return Err(ProgramError::InvalidAccountOwner);
         }
 
+        let (vesting_key, vesting_bump) = find_program_address(
+            &[
+                b"vesting",
+                &self.instruction_data.pubkey[..1],
+                &self.instruction_data.pubkey[1..33],
+            ],
+            &crate::ID
+        );
+
+        if vesting_key.ne(self.accounts.vesting.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if self.instruction_data.end_ts <= self.instruction_data.start_ts {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let vesting_bump = [vesting_bump];
+        let vesting_seeds = [
+            Seed::from(b"vesting"),
+            Seed::from(self.instruction_data.pubkey[..1].as_ref()),
+            Seed::from(self.instruction_data.pubkey[1..33].as_ref()),
+            Seed::from(&vesting_bump)
+        ];
+        let vesting_signers = [Signer::from(&vesting_seeds)];
+
+        CreateAccount {
+            from: self.accounts.payer,
+            to: self.accounts.vesting,
+            lamports: Rent::get()?.minimum_balance(VestingSchedule::LEN),
+            space: VestingSchedule::LEN as u64,
+            owner: &crate::ID,
+        }.invoke_signed(&vesting_signers)?;
+
+        VestingSchedule {
+            start_ts: self.instruction_data.start_ts,
+            end_ts: self.instruction_data.end_ts,
+            original: self.instruction_data.amount,
+            withdrawn: 0,
+        }.store(&mut self.accounts.vesting.try_borrow_mut_data()?)?;
+
         Transfer {
             from: self.accounts.payer,
             to: self.accounts.vault,
@@ -134,6 +196,124 @@ impl<'a> Deposit<'a> {
     }
 }
 
+/// Linear unlock schedule: lamports become withdrawable between `start_ts` and `end_ts`.
+#[repr(C, packed)]
+pub struct VestingSchedule {
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub original: u64,
+    pub withdrawn: u64,
+}
+
+impl VestingSchedule {
+    pub const LEN: usize = size_of::<Self>();
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            start_ts: i64::from_le_bytes(data[0..8].try_into().unwrap()),
+            end_ts: i64::from_le_bytes(data[8..16].try_into().unwrap()),
+            original: u64::from_le_bytes(data[16..24].try_into().unwrap()),
+            withdrawn: u64::from_le_bytes(data[24..32].try_into().unwrap()),
+        })
+    }
+
+    pub fn store(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        data[0..8].copy_from_slice(&self.start_ts.to_le_bytes());
+        data[8..16].copy_from_slice(&self.end_ts.to_le_bytes());
+        data[16..24].copy_from_slice(&self.original.to_le_bytes());
+        data[24..32].copy_from_slice(&self.withdrawn.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Lamports unlocked so far minus `withdrawn`, with the elapsed time clamped to
+    /// `[start_ts, end_ts]` and computed with checked u128 intermediates so
+    /// `original * elapsed` can't overflow. Returns `ArithmeticOverflow` when
+    /// `withdrawn` exceeds the unlocked amount.
+    pub fn available(&self, now: i64) -> Result<u64, ProgramError> {
+        if now <= self.start_ts {
+            return Ok(0);
+        }
+
+        if now >= self.end_ts {
+            return self.original.checked_sub(self.withdrawn).ok_or(ProgramError::ArithmeticOverflow);
+        }
+
+        let elapsed = (now - self.start_ts) as u128;
+        let span = (self.end_ts - self.start_ts) as u128;
+
+        let unlocked = (self.original as u128)
+            .checked_mul(elapsed)
+            .ok_or(ProgramError::ArithmeticOverflow)?
+            .checked_div(span)
+            .ok_or(ProgramError::ArithmeticOverflow)? as u64;
+
+        unlocked.checked_sub(self.withdrawn).ok_or(ProgramError::ArithmeticOverflow)
+    }
+}
+
+#[cfg(test)]
+mod vesting_schedule_tests {
+    use super::*;
+
+    fn schedule(start_ts: i64, end_ts: i64, original: u64, withdrawn: u64) -> VestingSchedule {
+        VestingSchedule { start_ts, end_ts, original, withdrawn }
+    }
+
+    #[test]
+    fn nothing_unlocked_before_start() {
+        let s = schedule(100, 200, 1_000, 0);
+        assert_eq!(s.available(0).unwrap(), 0);
+        assert_eq!(s.available(100).unwrap(), 0);
+    }
+
+    #[test]
+    fn fully_unlocked_at_and_after_end() {
+        let s = schedule(100, 200, 1_000, 0);
+        assert_eq!(s.available(200).unwrap(), 1_000);
+        assert_eq!(s.available(500).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn fully_unlocked_accounts_for_prior_withdrawals() {
+        let s = schedule(100, 200, 1_000, 400);
+        assert_eq!(s.available(200).unwrap(), 600);
+    }
+
+    #[test]
+    fn linearly_interpolates_mid_schedule() {
+        let s = schedule(0, 100, 1_000, 0);
+        assert_eq!(s.available(25).unwrap(), 250);
+        assert_eq!(s.available(50).unwrap(), 500);
+    }
+
+    #[test]
+    fn mid_schedule_subtracts_withdrawn() {
+        let s = schedule(0, 100, 1_000, 300);
+        assert_eq!(s.available(50).unwrap(), 200);
+    }
+
+    #[test]
+    fn withdrawn_ahead_of_unlocked_is_an_overflow_error() {
+        let s = schedule(0, 100, 1_000, 900);
+        assert!(s.available(10).unwrap_err().eq(&ProgramError::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn large_original_does_not_overflow_the_u128_intermediate() {
+        let s = schedule(0, 4, u64::MAX, 0);
+        assert_eq!(s.available(2).unwrap(), u64::MAX / 2);
+    }
+}
+
 
 //Withdraw
 
@@ -141,13 +321,15 @@ pub struct WithdrawAccounts<'a> {
     pub payer: &'a AccountInfo,
     pub vault: &'a AccountInfo,
     pub instructions: &'a AccountInfo,
+    pub vesting: &'a AccountInfo,
+    pub nonce: &'a AccountInfo,
 }
 
 impl<'a> TryFrom<&'a [AccountInfo]> for WithdrawAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
-        let [payer, vault, instructions, _system_program] = accounts else {
+        let [payer, vault, instructions, vesting, nonce, _system_program] = accounts else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
@@ -163,24 +345,317 @@ impl<'a> TryFrom<&'a [AccountInfo]> for WithdrawAccounts<'a> {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        Ok(Self { payer, vault, instructions })
+        if !vesting.is_owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        Ok(Self { payer, vault, instructions, vesting, nonce })
     }
 }
 
 pub struct WithdrawInstructionData {
-    pub bump: [u8; 1] 
+    pub bump: [u8; 1]
 }
 
 impl<'a> TryFrom<&'a  [u8]> for WithdrawInstructionData {
     type Error = ProgramError;
 
     fn try_from(data: &'a  [u8]) -> Result<Self, Self::Error> {
-        Ok(Self { 
+        Ok(Self {
             bump: [*data.first().ok_or(ProgramError::InvalidAccountData)?],
         })
     }
 }
 
+/// Below this, a partial withdrawal would leave the vault non-rent-exempt.
+fn rent_exempt_minimum(vault: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(Rent::get()?.minimum_balance(vault.data_len()))
+}
+
+/// Pure byte-layout decode of a withdrawal message: `tag(1) || payer(32) || expiry(8) || amount(8) || nonce(8)`.
+/// `expected_tag` binds the message to one instruction's discriminator, so a signature authorizing e.g. a
+/// [`Withdraw`] can't be redeemed against the [`TokenWithdraw`] or [`MultisigWithdraw`] vault instead.
+fn decode_withdraw_message(
+    message: &[u8],
+    expected_tag: u8,
+) -> Result<([u8; 32], i64, u64, u64), ProgramError> {
+    let (tag, rest) = message.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+    if tag.ne(&expected_tag) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (message_payer, rest) = rest
+        .split_at_checked(32)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (expiry, rest) = rest
+        .split_at_checked(8)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (amount, nonce) = rest
+        .split_at_checked(8)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+
+    let expiry = i64::from_le_bytes(
+        expiry
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?
+    );
+
+    let amount = u64::from_le_bytes(
+        amount
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?
+    );
+
+    let nonce = u64::from_le_bytes(
+        nonce
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?
+    );
+
+    Ok((message_payer.try_into().unwrap(), expiry, amount, nonce))
+}
+
+#[cfg(test)]
+mod decode_withdraw_message_tests {
+    use super::*;
+
+    fn message(tag: u8, payer: [u8; 32], expiry: i64, amount: u64, nonce: u64) -> [u8; 57] {
+        let mut buf = [0u8; 57];
+        buf[0] = tag;
+        buf[1..33].copy_from_slice(&payer);
+        buf[33..41].copy_from_slice(&expiry.to_le_bytes());
+        buf[41..49].copy_from_slice(&amount.to_le_bytes());
+        buf[49..57].copy_from_slice(&nonce.to_le_bytes());
+        buf
+    }
+
+    #[test]
+    fn decodes_a_well_formed_message() {
+        let payer = [7u8; 32];
+        let msg = message(*Withdraw::DISCRIMINATOR, payer, 1_000, 5_000, 3);
+
+        let (decoded_payer, expiry, amount, nonce) =
+            decode_withdraw_message(&msg, *Withdraw::DISCRIMINATOR).unwrap();
+
+        assert_eq!(decoded_payer, payer);
+        assert_eq!(expiry, 1_000);
+        assert_eq!(amount, 5_000);
+        assert_eq!(nonce, 3);
+    }
+
+    #[test]
+    fn rejects_a_message_tagged_for_a_different_instruction() {
+        let msg = message(*Withdraw::DISCRIMINATOR, [0u8; 32], 1_000, 5_000, 3);
+
+        assert!(decode_withdraw_message(&msg, *TokenWithdraw::DISCRIMINATOR).is_err());
+    }
+
+    #[test]
+    fn rejects_a_truncated_message() {
+        let msg = message(*Withdraw::DISCRIMINATOR, [0u8; 32], 1_000, 5_000, 3);
+
+        assert!(decode_withdraw_message(&msg[..msg.len() - 1], *Withdraw::DISCRIMINATOR).is_err());
+    }
+}
+
+/// Shared tail of a withdrawal message, checked against `payer` and the current clock.
+/// See [`decode_withdraw_message`] for the byte layout.
+fn parse_withdraw_message(
+    message: &[u8],
+    payer: &AccountInfo,
+    expected_tag: u8,
+) -> Result<(u64, u64), ProgramError> {
+    let (message_payer, expiry, amount, nonce) = decode_withdraw_message(message, expected_tag)?;
+
+    if payer.key().ne(&message_payer) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+
+    if now > expiry {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    Ok((amount, nonce))
+}
+
+/// Verifies the secp256r1 signature, introspected from the next instruction in the transaction, authorizing a withdrawal.
+fn verify_withdraw_authorization(
+    instructions: &AccountInfo,
+    payer: &AccountInfo,
+    expected_tag: u8,
+) -> Result<(Secp256r1Pubkey, u64, u64), ProgramError> {
+    let instructions = Instructions::try_from(instructions)?;
+    let ix: IntrospectedInstruction = instructions.get_instruction_relative(1)?;
+
+    let secp256r1_ix = Secp256r1Instruction::try_from(&ix)?;
+
+    if secp256r1_ix.num_signatures() != 1 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let signer = *secp256r1_ix.get_signer(0)?;
+
+    let message = secp256r1_ix.get_message_data(0)?;
+
+    let (amount, nonce) = parse_withdraw_message(message, payer, expected_tag)?;
+
+    Ok((signer, amount, nonce))
+}
+
+/// Multisig analogue of [`verify_withdraw_authorization`]: requires `config.threshold` distinct signers over the same message.
+fn verify_multisig_withdraw_authorization(
+    instructions: &AccountInfo,
+    payer: &AccountInfo,
+    config: &VaultConfig,
+    expected_tag: u8,
+) -> Result<(u64, u64), ProgramError> {
+    let instructions = Instructions::try_from(instructions)?;
+    let ix: IntrospectedInstruction = instructions.get_instruction_relative(1)?;
+
+    let secp256r1_ix = Secp256r1Instruction::try_from(&ix)?;
+
+    let num_signatures = secp256r1_ix.num_signatures();
+
+    if num_signatures == 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut counted = [false; MAX_MULTISIG_SIGNERS];
+    let mut valid_count: u8 = 0;
+    let mut common_message: Option<&[u8]> = None;
+
+    for i in 0..num_signatures {
+        let signer = *secp256r1_ix.get_signer(i)?;
+        let message = secp256r1_ix.get_message_data(i)?;
+
+        match common_message {
+            None => common_message = Some(message),
+            Some(expected) => {
+                if expected.ne(message) {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+            }
+        }
+
+        let member_index = config.signers[..config.signer_count as usize]
+            .iter()
+            .position(|candidate| candidate.eq(&signer));
+
+        if let Some(index) = member_index {
+            if !counted[index] {
+                counted[index] = true;
+                valid_count += 1;
+            }
+        }
+    }
+
+    if valid_count < config.threshold {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let message = common_message.ok_or(ProgramError::InvalidInstructionData)?;
+
+    parse_withdraw_message(message, payer, expected_tag)
+}
+
+/// Upper bound on the seed parts any nonce PDA is derived from (`b"nonce"` plus up to 3 more).
+const MAX_NONCE_SEED_PARTS: usize = 4;
+
+/// Shared "create-if-missing, else load + check owner" body behind [`load_or_init_nonce`] and
+/// [`load_or_init_config_nonce`], parameterized on the PDA's seed parts so the two nonce
+/// namespaces (per-signer-and-tag, per-config) can't drift out of sync with each other.
+fn load_or_init_nonce_pda(
+    nonce_account: &AccountInfo,
+    payer: &AccountInfo,
+    seeds: &[&[u8]],
+) -> Result<u64, ProgramError> {
+    const NONCE_LEN: usize = size_of::<u64>();
+
+    let (nonce_key, nonce_bump) = find_program_address(seeds, &crate::ID);
+
+    if nonce_key.ne(nonce_account.key()) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+
+    if nonce_account.lamports() == 0 {
+        let nonce_bump = [nonce_bump];
+
+        let mut signer_seeds: [Seed; MAX_NONCE_SEED_PARTS + 1] =
+            core::array::from_fn(|_| Seed::from(&[][..]));
+
+        for (slot, seed) in signer_seeds.iter_mut().zip(seeds.iter()) {
+            *slot = Seed::from(*seed);
+        }
+        signer_seeds[seeds.len()] = Seed::from(&nonce_bump);
+
+        let signers = [Signer::from(&signer_seeds[..seeds.len() + 1])];
+
+        CreateAccount {
+            from: payer,
+            to: nonce_account,
+            lamports: Rent::get()?.minimum_balance(NONCE_LEN),
+            space: NONCE_LEN as u64,
+            owner: &crate::ID,
+        }.invoke_signed(&signers)?;
+
+        return Ok(0);
+    }
+
+    if !nonce_account.is_owned_by(&crate::ID) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+
+    let data = nonce_account.try_borrow_data()?;
+
+    Ok(u64::from_le_bytes(
+        data.get(..NONCE_LEN)
+            .ok_or(ProgramError::InvalidAccountData)?
+            .try_into()
+            .unwrap()
+    ))
+}
+
+/// Loads (lazily creating) the per-signer, per-instruction-type nonce PDA; `tag` scopes the nonce
+/// namespace so consuming a [`Withdraw`] nonce can't exhaust a [`TokenWithdraw`]/[`RelayCpi`] signature
+/// for the same signer. Caller checks the returned value against the message nonce and persists via
+/// [`store_nonce`].
+fn load_or_init_nonce(
+    nonce_account: &AccountInfo,
+    payer: &AccountInfo,
+    signer: &Secp256r1Pubkey,
+    tag: u8,
+) -> Result<u64, ProgramError> {
+    let tag = [tag];
+
+    load_or_init_nonce_pda(
+        nonce_account,
+        payer,
+        &[b"nonce", &tag, signer[..1].as_ref(), signer[1..].as_ref()],
+    )
+}
+
+/// Config-keyed analogue of [`load_or_init_nonce`] — multisig has no single P-256 key to derive the nonce PDA from.
+fn load_or_init_config_nonce(
+    nonce_account: &AccountInfo,
+    payer: &AccountInfo,
+    config: &AccountInfo,
+) -> Result<u64, ProgramError> {
+    load_or_init_nonce_pda(nonce_account, payer, &[b"nonce", config.key().as_ref()])
+}
+
+fn store_nonce(nonce_account: &AccountInfo, value: u64) -> Result<(), ProgramError> {
+    let mut data = nonce_account.try_borrow_mut_data()?;
+
+    data.get_mut(..size_of::<u64>())
+        .ok_or(ProgramError::InvalidAccountData)?
+        .copy_from_slice(&value.to_le_bytes());
+
+    Ok(())
+}
+
 pub struct Withdraw<'a> {
     pub accounts: WithdrawAccounts<'a>,
     pub instruction_data: WithdrawInstructionData
@@ -201,42 +676,59 @@ impl<'a> Withdraw<'a> {
     pub const DISCRIMINATOR: &'a u8 = &1;
 
     pub fn process(&mut self) -> ProgramResult {
-        let instructions = Instructions::try_from(self.accounts.instructions)?;
-        let ix: IntrospectedInstruction = instructions.get_instruction_relative(1)?;
+        let (signer, amount, nonce) = verify_withdraw_authorization(
+            self.accounts.instructions,
+            self.accounts.payer,
+            *Self::DISCRIMINATOR,
+        )?;
 
-        let secp256r1_ix = Secp256r1Instruction::try_from(&ix)?;
+        let current_nonce = load_or_init_nonce(self.accounts.nonce, self.accounts.payer, &signer, *Self::DISCRIMINATOR)?;
 
-        if secp256r1_ix.num_signatures() != 1 {
-            return Err(ProgramError::InvalidInstructionData); 
+        if nonce != current_nonce {
+            return Err(ProgramError::InvalidInstructionData);
         }
 
-        let signer = *secp256r1_ix.get_signer(0)?;
+        store_nonce(self.accounts.nonce, current_nonce.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?)?;
+
+        let now = Clock::get()?.unix_timestamp;
 
-        let (payer, expiry) =secp256r1_ix
-            .get_message_data(0)?
-            .split_at_checked(32)
-            .ok_or(ProgramError::InvalidInstructionData)?;
+        let vault_lamports = self.accounts.vault.lamports();
 
-        if self.accounts.payer.key().ne(payer) {
-            return Err(ProgramError::InvalidAccountOwner); 
+        if amount > vault_lamports {
+            return Err(ProgramError::InsufficientFunds);
         }
 
-        let now = Clock::get()?.unix_timestamp;
-        let expiry = i64::from_le_bytes(
-            expiry
-                .try_into()
-                .map_err(|_| ProgramError::InvalidInstructionData)?
+        let remaining = vault_lamports - amount;
+
+        if remaining != 0 && remaining < rent_exempt_minimum(self.accounts.vault)? {
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        let (vesting_key, _) = find_program_address(
+            &[b"vesting", signer[..1].as_ref(), signer[1..].as_ref()],
+            &crate::ID
         );
 
-        if now > expiry {
-            return Err(ProgramError::InvalidInstructionData); 
+        if vesting_key.ne(self.accounts.vesting.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let mut vesting_data = self.accounts.vesting.try_borrow_mut_data()?;
+        let mut schedule = VestingSchedule::load(&vesting_data)?;
+
+        if amount > schedule.available(now)? {
+            return Err(ProgramError::InsufficientFunds);
         }
 
+        schedule.withdrawn = schedule.withdrawn.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+        schedule.store(&mut vesting_data)?;
+        drop(vesting_data);
+
         let seeds = [
             Seed::from(b"vault"),
             Seed::from(signer[..1].as_ref()),
             Seed::from(signer[1..].as_ref()),
-            Seed::from(&self.instruction_data.bump) 
+            Seed::from(&self.instruction_data.bump)
         ];
 
         let signers = [Signer::from(&seeds)];
@@ -244,10 +736,1102 @@ impl<'a> Withdraw<'a> {
         Transfer {
             from: self.accounts.vault,
             to: self.accounts.payer,
-            lamports: self.accounts.vault.lamports() 
+            lamports: amount
         }.invoke_signed(&signers)
+    }
+}
+
+
+//TokenDeposit / TokenWithdraw
+
+fn token_accounts_share_mint(a: &AccountInfo, b: &AccountInfo) -> Result<bool, ProgramError> {
+    let a = TokenAccount::from_account_info(a)?;
+    let b = TokenAccount::from_account_info(b)?;
+
+    Ok(a.mint().eq(b.mint()))
+}
+
+pub struct TokenDepositAccounts<'a> {
+    pub payer: &'a AccountInfo,
+    pub payer_token_account: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub vault_token_account: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for TokenDepositAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [payer, payer_token_account, vault, vault_token_account, token_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !payer.is_signer() {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if token_program.key().ne(&pinocchio_token::ID) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
 
+        if !token_accounts_share_mint(payer_token_account, vault_token_account)? {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let vault_token_account_owner = *TokenAccount::from_account_info(vault_token_account)?.owner();
+
+        if vault_token_account_owner.ne(vault.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        Ok(Self { payer, payer_token_account, vault, vault_token_account, token_program })
+    }
+}
+
+#[repr(C, packed)]
+pub struct TokenDepositInstructionData {
+    pub pubkey: Secp256r1Pubkey,
+    pub amount: u64
+}
+
+impl<'a> TryFrom<&'a [u8]> for TokenDepositInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<Self>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let (pubkey_bytes, amount_bytes) = data.split_at(size_of::<Secp256r1Pubkey>());
+
+        Ok(Self {
+            pubkey: pubkey_bytes.try_into().unwrap(),
+            amount: u64::from_le_bytes(amount_bytes.try_into().unwrap())
+        })
+    }
+}
+
+pub struct TokenDeposit<'a> {
+    pub accounts: TokenDepositAccounts<'a>,
+    pub instruction_data: TokenDepositInstructionData
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for TokenDeposit<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = TokenDepositAccounts::try_from(accounts)?;
+        let instruction_data = TokenDepositInstructionData::try_from(data)?;
+
+        Ok(Self { accounts, instruction_data })
+    }
+}
+
+impl<'a> TokenDeposit<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &2;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let (vault_key, _) = find_program_address(
+            &[
+                b"vault",
+                &self.instruction_data.pubkey[..1],
+                &self.instruction_data.pubkey[1..33],
+            ],
+            &crate::ID
+        );
 
+        if vault_key.ne(self.accounts.vault.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        TokenTransfer {
+            from: self.accounts.payer_token_account,
+            to: self.accounts.vault_token_account,
+            authority: self.accounts.payer,
+            amount: self.instruction_data.amount
+        }.invoke()
+    }
+}
+
+pub struct TokenWithdrawAccounts<'a> {
+    pub payer: &'a AccountInfo,
+    pub payer_token_account: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub vault_token_account: &'a AccountInfo,
+    pub instructions: &'a AccountInfo,
+    pub nonce: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for TokenWithdrawAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [payer, payer_token_account, vault, vault_token_account, instructions, nonce, token_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !payer.is_signer() {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if token_program.key().ne(&pinocchio_token::ID) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if !token_accounts_share_mint(payer_token_account, vault_token_account)? {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let vault_token_account_owner = *TokenAccount::from_account_info(vault_token_account)?.owner();
+
+        if vault_token_account_owner.ne(vault.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        Ok(Self { payer, payer_token_account, vault, vault_token_account, instructions, nonce, token_program })
+    }
+}
+
+pub struct TokenWithdrawInstructionData {
+    pub bump: [u8; 1]
+}
+
+impl<'a> TryFrom<&'a [u8]> for TokenWithdrawInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            bump: [*data.first().ok_or(ProgramError::InvalidAccountData)?],
+        })
+    }
+}
+
+pub struct TokenWithdraw<'a> {
+    pub accounts: TokenWithdrawAccounts<'a>,
+    pub instruction_data: TokenWithdrawInstructionData
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for TokenWithdraw<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = TokenWithdrawAccounts::try_from(accounts)?;
+        let instruction_data = TokenWithdrawInstructionData::try_from(data)?;
+
+        Ok(Self { accounts, instruction_data })
+    }
+}
+
+impl<'a> TokenWithdraw<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &3;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let (signer, amount, nonce) = verify_withdraw_authorization(
+            self.accounts.instructions,
+            self.accounts.payer,
+            *Self::DISCRIMINATOR,
+        )?;
+
+        let current_nonce = load_or_init_nonce(self.accounts.nonce, self.accounts.payer, &signer, *Self::DISCRIMINATOR)?;
+
+        if nonce != current_nonce {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        store_nonce(self.accounts.nonce, current_nonce.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?)?;
+
+        let (vault_key, _) = find_program_address(
+            &[b"vault", signer[..1].as_ref(), signer[1..].as_ref()],
+            &crate::ID
+        );
+
+        if vault_key.ne(self.accounts.vault.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let seeds = [
+            Seed::from(b"vault"),
+            Seed::from(signer[..1].as_ref()),
+            Seed::from(signer[1..].as_ref()),
+            Seed::from(&self.instruction_data.bump)
+        ];
+
+        let signers = [Signer::from(&seeds)];
+
+        TokenTransfer {
+            from: self.accounts.vault_token_account,
+            to: self.accounts.payer_token_account,
+            authority: self.accounts.vault,
+            amount
+        }.invoke_signed(&signers)
+    }
+}
+
+
+//ConfigureMultisig / MultisigWithdraw
+
+/// Upper bound on signers in a `VaultConfig`.
+pub const MAX_MULTISIG_SIGNERS: usize = 8;
+
+/// An M-of-N authorized set; the vault PDA derives from this account's own address.
+///
+/// Unlike the single-key vault, the multisig vault has no `Deposit`-equivalent instruction
+/// and so is never paired with a [`VestingSchedule`] — withdrawals against it are gated only
+/// by reaching `threshold` signatures, not by any unlock schedule. That's intentional, not an
+/// oversight.
+#[repr(C, packed)]
+pub struct VaultConfig {
+    pub threshold: u8,
+    pub signer_count: u8,
+    pub signers: [Secp256r1Pubkey; MAX_MULTISIG_SIGNERS],
+}
+
+impl VaultConfig {
+    pub const LEN: usize = size_of::<Self>();
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let threshold = data[0];
+        let signer_count = data[1];
+        let mut signers = [[0u8; 33]; MAX_MULTISIG_SIGNERS];
+
+        for (i, slot) in signers.iter_mut().enumerate() {
+            let start = 2 + i * 33;
+            slot.copy_from_slice(&data[start..start + 33]);
+        }
+
+        Ok(Self { threshold, signer_count, signers })
+    }
+
+    pub fn store(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        data[0] = self.threshold;
+        data[1] = self.signer_count;
+
+        for (i, slot) in self.signers.iter().enumerate() {
+            let start = 2 + i * 33;
+            data[start..start + 33].copy_from_slice(slot);
+        }
+
+        Ok(())
+    }
+}
+
+/// Chains `threshold` and the sorted `signers` through `find_program_address` into a commitment, so the config PDA can't be front-run with a different authorized set.
+fn commit_signer_set(threshold: u8, signers: &[Secp256r1Pubkey]) -> Pubkey {
+    let mut acc = crate::ID;
+
+    for signer in signers {
+        let (next, _) = find_program_address(
+            &[acc.as_ref(), signer[..1].as_ref(), signer[1..].as_ref()],
+            &crate::ID
+        );
+        acc = next;
+    }
+
+    let (commitment, _) = find_program_address(&[acc.as_ref(), &[threshold]], &crate::ID);
+    commitment
+}
+
+pub struct ConfigureMultisigAccounts<'a> {
+    pub payer: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for ConfigureMultisigAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [payer, config, _system_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !payer.is_signer() {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if config.lamports().ne(&0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self { payer, config })
+    }
+}
+
+#[repr(C, packed)]
+pub struct ConfigureMultisigInstructionData {
+    pub threshold: u8,
+    pub signer_count: u8,
+    pub signers: [Secp256r1Pubkey; MAX_MULTISIG_SIGNERS],
+}
+
+impl<'a> TryFrom<&'a [u8]> for ConfigureMultisigInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<Self>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let threshold = data[0];
+        let signer_count = data[1];
+        let mut signers = [[0u8; 33]; MAX_MULTISIG_SIGNERS];
+
+        for (i, slot) in signers.iter_mut().enumerate() {
+            let start = 2 + i * 33;
+            slot.copy_from_slice(&data[start..start + 33]);
+        }
+
+        Ok(Self {
+            threshold,
+            signer_count,
+            signers,
+        })
+    }
+}
+
+pub struct ConfigureMultisig<'a> {
+    pub accounts: ConfigureMultisigAccounts<'a>,
+    pub instruction_data: ConfigureMultisigInstructionData
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for ConfigureMultisig<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = ConfigureMultisigAccounts::try_from(accounts)?;
+        let instruction_data = ConfigureMultisigInstructionData::try_from(data)?;
+
+        Ok(Self { accounts, instruction_data })
+    }
+}
+
+impl<'a> ConfigureMultisig<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &4;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let signer_count = self.instruction_data.signer_count as usize;
+
+        if signer_count == 0 || signer_count > MAX_MULTISIG_SIGNERS {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        if self.instruction_data.threshold == 0 || self.instruction_data.threshold as usize > signer_count {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let mut sorted_signers = self.instruction_data.signers;
+        sorted_signers[..signer_count].sort();
+
+        let commitment = commit_signer_set(self.instruction_data.threshold, &sorted_signers[..signer_count]);
+
+        let (config_key, config_bump) = find_program_address(
+            &[b"config", commitment.as_ref()],
+            &crate::ID
+        );
+
+        if config_key.ne(self.accounts.config.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let config_bump = [config_bump];
+        let seeds = [
+            Seed::from(b"config"),
+            Seed::from(commitment.as_ref()),
+            Seed::from(&config_bump)
+        ];
+        let signers = [Signer::from(&seeds)];
+
+        CreateAccount {
+            from: self.accounts.payer,
+            to: self.accounts.config,
+            lamports: Rent::get()?.minimum_balance(VaultConfig::LEN),
+            space: VaultConfig::LEN as u64,
+            owner: &crate::ID,
+        }.invoke_signed(&signers)?;
+
+        VaultConfig {
+            threshold: self.instruction_data.threshold,
+            signer_count: self.instruction_data.signer_count,
+            signers: sorted_signers,
+        }.store(&mut self.accounts.config.try_borrow_mut_data()?)
+    }
+}
+
+/// No `vesting` account here by design — see [`VaultConfig`] for why the M-of-N vault isn't
+/// subject to a vesting schedule.
+pub struct MultisigWithdrawAccounts<'a> {
+    pub payer: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub instructions: &'a AccountInfo,
+    pub nonce: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for MultisigWithdrawAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [payer, vault, instructions, nonce, config, _system_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !payer.is_signer() {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if !vault.is_owned_by(&pinocchio_system::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if vault.lamports().eq(&0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !config.is_owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        Ok(Self { payer, vault, instructions, nonce, config })
+    }
+}
+
+pub struct MultisigWithdrawInstructionData {
+    pub bump: [u8; 1]
+}
+
+impl<'a> TryFrom<&'a [u8]> for MultisigWithdrawInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            bump: [*data.first().ok_or(ProgramError::InvalidAccountData)?],
+        })
+    }
+}
+
+pub struct MultisigWithdraw<'a> {
+    pub accounts: MultisigWithdrawAccounts<'a>,
+    pub instruction_data: MultisigWithdrawInstructionData
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for MultisigWithdraw<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = MultisigWithdrawAccounts::try_from(accounts)?;
+        let instruction_data = MultisigWithdrawInstructionData::try_from(data)?;
+
+        Ok(Self { accounts, instruction_data })
+    }
+}
+
+impl<'a> MultisigWithdraw<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &5;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config_data = self.accounts.config.try_borrow_data()?;
+        let config = VaultConfig::load(&config_data)?;
+        drop(config_data);
+
+        let (amount, nonce) = verify_multisig_withdraw_authorization(
+            self.accounts.instructions,
+            self.accounts.payer,
+            &config,
+            *Self::DISCRIMINATOR,
+        )?;
+
+        let current_nonce = load_or_init_config_nonce(self.accounts.nonce, self.accounts.payer, self.accounts.config)?;
+
+        if nonce != current_nonce {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        store_nonce(self.accounts.nonce, current_nonce.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?)?;
+
+        let (vault_key, _) = find_program_address(
+            &[b"vault", self.accounts.config.key().as_ref()],
+            &crate::ID
+        );
+
+        if vault_key.ne(self.accounts.vault.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let vault_lamports = self.accounts.vault.lamports();
+
+        if amount > vault_lamports {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        let remaining = vault_lamports - amount;
+
+        if remaining != 0 && remaining < rent_exempt_minimum(self.accounts.vault)? {
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        let seeds = [
+            Seed::from(b"vault"),
+            Seed::from(self.accounts.config.key().as_ref()),
+            Seed::from(&self.instruction_data.bump)
+        ];
+
+        let signers = [Signer::from(&seeds)];
+
+        Transfer {
+            from: self.accounts.vault,
+            to: self.accounts.payer,
+            lamports: amount
+        }.invoke_signed(&signers)
+    }
+}
+
+
+//ConfigureRelay / RelayCpi
+
+/// Upper bound on whitelisted programs in a `RelayConfig`.
+pub const MAX_RELAY_PROGRAMS: usize = 4;
+
+/// Upper bound on accounts per relayed CPI (writable flags fit a `u16` bitmask).
+pub const MAX_RELAY_ACCOUNTS: usize = 16;
+
+/// Upper bound on the relayed instruction's data payload.
+pub const MAX_RELAY_IX_DATA: usize = 256;
+
+/// Programs a vault's P-256 key may direct the vault to CPI into via [`RelayCpi`].
+#[repr(C, packed)]
+pub struct RelayConfig {
+    pub count: u8,
+    pub programs: [Pubkey; MAX_RELAY_PROGRAMS],
+}
+
+impl RelayConfig {
+    pub const LEN: usize = size_of::<Self>();
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let count = data[0];
+        let mut programs = [[0u8; 32]; MAX_RELAY_PROGRAMS];
+
+        for (i, slot) in programs.iter_mut().enumerate() {
+            let start = 1 + i * 32;
+            slot.copy_from_slice(&data[start..start + 32]);
+        }
+
+        Ok(Self { count, programs })
+    }
+
+    pub fn store(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        data[0] = self.count;
+
+        for (i, slot) in self.programs.iter().enumerate() {
+            let start = 1 + i * 32;
+            data[start..start + 32].copy_from_slice(slot);
+        }
+
+        Ok(())
+    }
+
+    pub fn allows(&self, program_id: &Pubkey) -> bool {
+        self.programs[..self.count as usize]
+            .iter()
+            .any(|candidate| candidate.eq(program_id))
+    }
+}
+
+pub struct ConfigureRelayAccounts<'a> {
+    pub payer: &'a AccountInfo,
+    pub relay: &'a AccountInfo,
+    pub instructions: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for ConfigureRelayAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [payer, relay, instructions, _system_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !payer.is_signer() {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if relay.lamports().ne(&0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self { payer, relay, instructions })
+    }
+}
+
+#[repr(C, packed)]
+pub struct ConfigureRelayInstructionData {
+    pub pubkey: Secp256r1Pubkey,
+    pub count: u8,
+    pub programs: [Pubkey; MAX_RELAY_PROGRAMS],
+}
+
+impl<'a> TryFrom<&'a [u8]> for ConfigureRelayInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<Self>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let (pubkey_bytes, rest) = data.split_at(size_of::<Secp256r1Pubkey>());
+        let count = rest[0];
+        let mut programs = [[0u8; 32]; MAX_RELAY_PROGRAMS];
+
+        for (i, slot) in programs.iter_mut().enumerate() {
+            let start = 1 + i * 32;
+            slot.copy_from_slice(&rest[start..start + 32]);
+        }
+
+        Ok(Self {
+            pubkey: pubkey_bytes.try_into().unwrap(),
+            count,
+            programs,
+        })
+    }
+}
+
+pub struct ConfigureRelay<'a> {
+    pub accounts: ConfigureRelayAccounts<'a>,
+    pub instruction_data: ConfigureRelayInstructionData
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for ConfigureRelay<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = ConfigureRelayAccounts::try_from(accounts)?;
+        let instruction_data = ConfigureRelayInstructionData::try_from(data)?;
+
+        Ok(Self { accounts, instruction_data })
+    }
+}
+
+/// Verifies `pubkey` itself signed this exact program list, so the one-shot relay account can't be squatted by someone else.
+fn verify_configure_relay_authorization(
+    instructions: &AccountInfo,
+    payer: &AccountInfo,
+    pubkey: &Secp256r1Pubkey,
+    count: u8,
+    programs: &[Pubkey],
+) -> Result<(), ProgramError> {
+    let instructions = Instructions::try_from(instructions)?;
+    let ix: IntrospectedInstruction = instructions.get_instruction_relative(1)?;
+
+    let secp256r1_ix = Secp256r1Instruction::try_from(&ix)?;
+
+    if secp256r1_ix.num_signatures() != 1 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let signer = *secp256r1_ix.get_signer(0)?;
+
+    if signer.ne(pubkey) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+
+    let message = secp256r1_ix.get_message_data(0)?;
+
+    let (tag, rest) = message.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+    if tag.ne(ConfigureRelay::DISCRIMINATOR) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (message_payer, rest) = rest
+        .split_at_checked(32)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (expiry, rest) = rest
+        .split_at_checked(8)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (message_count, rest) = rest.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+    if payer.key().ne(message_payer) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let expiry = i64::from_le_bytes(
+        expiry
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?
+    );
+
+    if now > expiry {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    if message_count.ne(&count) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    if rest.len() != programs.len() * 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    for (i, program) in programs.iter().enumerate() {
+        if program.as_ref().ne(&rest[i * 32..i * 32 + 32]) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> ConfigureRelay<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &6;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let count = self.instruction_data.count as usize;
+
+        if count == 0 || count > MAX_RELAY_PROGRAMS {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        verify_configure_relay_authorization(
+            self.accounts.instructions,
+            self.accounts.payer,
+            &self.instruction_data.pubkey,
+            self.instruction_data.count,
+            &self.instruction_data.programs[..count],
+        )?;
+
+        let (relay_key, relay_bump) = find_program_address(
+            &[
+                b"relay",
+                &self.instruction_data.pubkey[..1],
+                &self.instruction_data.pubkey[1..33],
+            ],
+            &crate::ID
+        );
+
+        if relay_key.ne(self.accounts.relay.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let relay_bump = [relay_bump];
+        let seeds = [
+            Seed::from(b"relay"),
+            Seed::from(self.instruction_data.pubkey[..1].as_ref()),
+            Seed::from(self.instruction_data.pubkey[1..33].as_ref()),
+            Seed::from(&relay_bump)
+        ];
+        let signers = [Signer::from(&seeds)];
+
+        CreateAccount {
+            from: self.accounts.payer,
+            to: self.accounts.relay,
+            lamports: Rent::get()?.minimum_balance(RelayConfig::LEN),
+            space: RelayConfig::LEN as u64,
+            owner: &crate::ID,
+        }.invoke_signed(&signers)?;
+
+        RelayConfig {
+            count: self.instruction_data.count,
+            programs: self.instruction_data.programs,
+        }.store(&mut self.accounts.relay.try_borrow_mut_data()?)
+    }
+}
+
+pub struct RelayCpiAccounts<'a> {
+    pub payer: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub instructions: &'a AccountInfo,
+    pub nonce: &'a AccountInfo,
+    pub relay: &'a AccountInfo,
+    pub target_program: &'a AccountInfo,
+    pub remaining: &'a [AccountInfo],
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for RelayCpiAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [payer, vault, instructions, nonce, relay, target_program, remaining @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !payer.is_signer() {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if !vault.is_owned_by(&pinocchio_system::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if !relay.is_owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if remaining.len() > MAX_RELAY_ACCOUNTS {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self { payer, vault, instructions, nonce, relay, target_program, remaining })
+    }
+}
+
+#[repr(C, packed)]
+pub struct RelayCpiInstructionData {
+    pub bump: [u8; 1],
+    pub data_len: u16,
+    pub writable_mask: u16,
+    pub data: [u8; MAX_RELAY_IX_DATA],
+}
+
+impl<'a> TryFrom<&'a [u8]> for RelayCpiInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<Self>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let (bump_bytes, rest) = data.split_at(1);
+        let (data_len_bytes, rest) = rest.split_at(2);
+        let (writable_mask_bytes, ix_data) = rest.split_at(2);
+
+        let data_len = u16::from_le_bytes(data_len_bytes.try_into().unwrap());
+
+        if data_len as usize > MAX_RELAY_IX_DATA {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let mut buf = [0u8; MAX_RELAY_IX_DATA];
+        buf.copy_from_slice(ix_data);
+
+        Ok(Self {
+            bump: [bump_bytes[0]],
+            data_len,
+            writable_mask: u16::from_le_bytes(writable_mask_bytes.try_into().unwrap()),
+            data: buf,
+        })
+    }
+}
+
+pub struct RelayCpi<'a> {
+    pub accounts: RelayCpiAccounts<'a>,
+    pub instruction_data: RelayCpiInstructionData
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for RelayCpi<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = RelayCpiAccounts::try_from(accounts)?;
+        let instruction_data = RelayCpiInstructionData::try_from(data)?;
+
+        Ok(Self { accounts, instruction_data })
+    }
+}
+
+/// Verifies the signed message binds `target_program`/`data`/`remaining` to this exact CPI, so a withdrawal signature can't be replayed as a relay authorization.
+fn verify_relay_authorization(
+    instructions: &AccountInfo,
+    payer: &AccountInfo,
+    target_program: &AccountInfo,
+    data: &[u8],
+    remaining: &[AccountInfo],
+) -> Result<(Secp256r1Pubkey, u64), ProgramError> {
+    let instructions = Instructions::try_from(instructions)?;
+    let ix: IntrospectedInstruction = instructions.get_instruction_relative(1)?;
+
+    let secp256r1_ix = Secp256r1Instruction::try_from(&ix)?;
+
+    if secp256r1_ix.num_signatures() != 1 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let signer = *secp256r1_ix.get_signer(0)?;
+    let message = secp256r1_ix.get_message_data(0)?;
+
+    let (tag, rest) = message.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+    if tag.ne(RelayCpi::DISCRIMINATOR) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (message_payer, rest) = rest
+        .split_at_checked(32)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (expiry, rest) = rest
+        .split_at_checked(8)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (nonce, rest) = rest
+        .split_at_checked(8)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (signed_target_program, rest) = rest
+        .split_at_checked(32)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (data_len, rest) = rest
+        .split_at_checked(2)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let data_len = u16::from_le_bytes(
+        data_len
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?
+    ) as usize;
+    let (signed_data, rest) = rest
+        .split_at_checked(data_len)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let (account_count, rest) = rest.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+    if payer.key().ne(message_payer) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let expiry = i64::from_le_bytes(
+        expiry
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?
+    );
+
+    if now > expiry {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let nonce = u64::from_le_bytes(
+        nonce
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?
+    );
+
+    if target_program.key().ne(signed_target_program) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if signed_data.ne(data) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    if *account_count as usize != remaining.len() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    if rest.len() != remaining.len() * 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    for (i, account) in remaining.iter().enumerate() {
+        if account.key().as_ref().ne(&rest[i * 32..i * 32 + 32]) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+    }
+
+    Ok((signer, nonce))
+}
+
+impl<'a> RelayCpi<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &7;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let (signer, nonce) = verify_relay_authorization(
+            self.accounts.instructions,
+            self.accounts.payer,
+            self.accounts.target_program,
+            &self.instruction_data.data[..self.instruction_data.data_len as usize],
+            self.accounts.remaining,
+        )?;
+
+        let current_nonce = load_or_init_nonce(self.accounts.nonce, self.accounts.payer, &signer, *Self::DISCRIMINATOR)?;
+
+        if nonce != current_nonce {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        store_nonce(self.accounts.nonce, current_nonce.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?)?;
+
+        let (vault_key, _) = find_program_address(
+            &[b"vault", signer[..1].as_ref(), signer[1..].as_ref()],
+            &crate::ID
+        );
+
+        if vault_key.ne(self.accounts.vault.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let (relay_key, _) = find_program_address(
+            &[b"relay", signer[..1].as_ref(), signer[1..].as_ref()],
+            &crate::ID
+        );
+
+        if relay_key.ne(self.accounts.relay.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let relay_data = self.accounts.relay.try_borrow_data()?;
+        let relay = RelayConfig::load(&relay_data)?;
+        drop(relay_data);
+
+        if !relay.allows(self.accounts.target_program.key()) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if self.accounts.remaining.len() > MAX_RELAY_ACCOUNTS {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Only the vault's own key may be marked as a signer in the relayed
+        // CPI, regardless of what the caller asked for, so a malicious
+        // target program can't ride the vault's signer seeds to reach
+        // accounts the caller doesn't actually control.
+        let mut metas: [AccountMeta; MAX_RELAY_ACCOUNTS] =
+            core::array::from_fn(|_| AccountMeta::readonly(self.accounts.vault.key()));
+        let mut cpi_accounts: [&AccountInfo; MAX_RELAY_ACCOUNTS] =
+            core::array::from_fn(|_| self.accounts.vault);
+
+        for (i, account) in self.accounts.remaining.iter().enumerate() {
+            let is_writable = self.instruction_data.writable_mask & (1 << i) != 0;
+            let is_signer = account.key().eq(self.accounts.vault.key());
+
+            metas[i] = if is_signer {
+                AccountMeta::writable_signer(account.key())
+            } else if is_writable {
+                AccountMeta::writable(account.key())
+            } else {
+                AccountMeta::readonly(account.key())
+            };
+
+            cpi_accounts[i] = account;
+        }
+
+        let instruction = Instruction {
+            program_id: self.accounts.target_program.key(),
+            accounts: &metas[..self.accounts.remaining.len()],
+            data: &self.instruction_data.data[..self.instruction_data.data_len as usize],
+        };
+
+        let seeds = [
+            Seed::from(b"vault"),
+            Seed::from(signer[..1].as_ref()),
+            Seed::from(signer[1..].as_ref()),
+            Seed::from(&self.instruction_data.bump)
+        ];
+
+        let signers = [Signer::from(&seeds)];
 
+        invoke_signed(&instruction, &cpi_accounts[..self.accounts.remaining.len()], &signers)
     }
 }
\ No newline at end of file